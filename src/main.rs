@@ -1,26 +1,39 @@
 use std::{
     borrow::Cow,
+    io::Write,
     ops::Deref,
     path::PathBuf,
 };
 
 use chrono::{
     DateTime,
+    Duration,
     Local,
+    LocalResult,
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    TimeZone,
+    Timelike,
     Utc,
 };
 use color_eyre::eyre::{
+    bail,
     eyre,
     Error,
 };
 use prettytable::{
+    color,
     format::consts::FORMAT_CLEAN,
     Attr,
     Cell,
     Row,
     Table,
 };
-use serde::Deserialize;
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use structopt::StructOpt;
 
 /// Shows the current time in multiple time zones.
@@ -38,8 +51,15 @@ struct Args {
     /// Optionally you can specify a custom name for the clock. If omitted, the
     /// name of the time zone is used.
     ///
+    /// You can also specify a `strftime`-style `format` string, either
+    /// globally or per clock (which overrides the global one). It defaults
+    /// to "%H:%M:%S".
+    ///
     /// Example:
     ///
+    ///     # Shown for every clock that doesn't specify its own format.
+    ///     format = "%H:%M:%S"
+    ///
     ///     # Local clock
     ///     [[clocks]]
     ///
@@ -53,65 +73,396 @@ struct Args {
     ///     [[clocks]]
     ///     name = "New York"
     ///     tz = "America/New_York"
+    ///     format = "%a %Y-%m-%d %I:%M %p"
+    ///     work_hours = [9, 17]
     #[structopt(verbatim_doc_comment, short, long)]
     config: Option<PathBuf>,
-    /*
+
     /// Instead of displaying the current time, use the specified time.
-    // FIXME: Parse properly
-    #[structopt(short, long)]
-    time: Option<NaiveDateTime>,
+    ///
+    /// This can be:
+    ///
+    ///  - An RFC 3339 / ISO 8601 timestamp with a UTC offset, e.g.
+    ///    `2024-06-01T14:30:00-04:00`.
+    ///  - A bare date-time or time, e.g. `2024-06-01 14:30` or `14:30`,
+    ///    which is interpreted in the zone given by `--in` (or the local
+    ///    zone, or UTC if `--utc` is given).
+    #[structopt(verbatim_doc_comment, short, long)]
+    time: Option<String>,
+
+    /// Timezone to interpret a bare `--time` in. Defaults to the local
+    /// timezone. Can't be combined with `--utc`.
+    #[structopt(long = "in")]
+    in_tz: Option<Tz>,
 
-    /// If `--time` is used, it will be interpreted as UTC.
+    /// If `--time` is used and is a bare date-time or time, interpret it as
+    /// UTC rather than the local (or `--in`) timezone.
     #[structopt(short, long)]
     utc: bool,
-    */
+
+    /// Output format: `table`, `json` or `csv`. Only `table` is supported
+    /// together with `--plan`.
+    #[structopt(long, default_value = "table")]
+    output: OutputFormat,
+
+    /// Show a meeting-planner grid of every hour of a day across all
+    /// configured clocks, instead of the current time for each.
+    #[structopt(long)]
+    plan: bool,
+
+    /// Day to show in `--plan` mode, as `YYYY-MM-DD`. Defaults to today in
+    /// the local timezone.
+    #[structopt(long)]
+    day: Option<NaiveDate>,
+
+    /// Redraw the clocks every `--interval` seconds instead of printing
+    /// once and exiting. Can't be combined with `--time`.
+    #[structopt(long)]
+    watch: bool,
+
+    /// How often, in seconds, to redraw in `--watch` mode.
+    #[structopt(long, default_value = "1")]
+    interval: u64,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!(
+                "Invalid output format: `{}`. Expected `table`, `json` or `csv`.",
+                s
+            )),
+        }
+    }
+}
+
+/// The default `strftime` format used when neither the clock nor the config
+/// specify one.
+const DEFAULT_FORMAT: &str = "%H:%M:%S";
+
 #[derive(Clone, Debug, Deserialize, Default)]
 struct Clock {
     name: Option<String>,
     tz: Option<Tz>,
+
+    /// `strftime`-style format string for this clock. Overrides the
+    /// config-level `format`, if any.
+    format: Option<String>,
+
+    /// Local working hours `[start, end)` (in 24h time) used to highlight
+    /// this clock's cells in `--plan` mode, e.g. `work_hours = [9, 17]`.
+    work_hours: Option<(u32, u32)>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 struct Config {
     #[serde(default)]
     clocks: Vec<Clock>,
+
+    /// Default `strftime`-style format string used for clocks that don't
+    /// specify their own `format`.
+    format: Option<String>,
+}
+
+/// A `DateTime<Utc>` paired with the named timezone it should be displayed
+/// in, so that both the instant and the zone round-trip through
+/// serialization. Serializes as `"<rfc3339 in that zone> <IANA zone id>"`,
+/// e.g. `"2024-06-01T08:30:00-04:00 America/New_York"`.
+#[derive(Clone, Debug)]
+struct DateTimeTz {
+    instant: DateTime<Utc>,
+    tz: Tz,
+}
+
+impl Serialize for DateTimeTz {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let local = self.instant.with_timezone(&self.tz.0);
+        serializer.serialize_str(&format!("{} {}", local.to_rfc3339(), self.tz.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for DateTimeTz {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s: Cow<'de, str> = Deserialize::deserialize(deserializer)?;
+        let (instant, tz) = s
+            .rsplit_once(' ')
+            .ok_or_else(|| serde::de::Error::custom("expected `<rfc3339> <tz>`"))?;
+        let instant = DateTime::parse_from_rfc3339(instant)
+            .map_err(serde::de::Error::custom)?
+            .with_timezone(&Utc);
+        let tz: Tz = tz.parse().map_err(serde::de::Error::custom)?;
+        Ok(Self { instant, tz })
+    }
+}
+
+/// One row of clock output, shared between the table, JSON and CSV
+/// renderers.
+#[derive(Clone, Debug, Serialize)]
+struct ClockRecord {
+    name: String,
+    tz: String,
+    local_time: String,
+    offset: String,
+    dst: String,
+    instant: DateTimeTz,
+}
+
+/// Determines the IANA zone the system's local timezone corresponds to, so
+/// clocks without an explicit `tz` can still be serialized with a named
+/// zone. Falls back to UTC if it can't be determined.
+fn local_tz() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse::<chrono_tz::Tz>().ok())
+        .map(Tz)
+        .unwrap_or(Tz(chrono_tz::UTC))
+}
+
+fn clock_records(clocks: &[Clock], time: DateTime<Utc>, default_format: &str) -> Vec<ClockRecord> {
+    clocks
+        .iter()
+        .map(|clock| {
+            let format = clock.format.as_deref().unwrap_or(default_format);
+            let tz = clock.tz.clone().unwrap_or_else(local_tz);
+            let local_time = time.with_timezone(&tz.0);
+
+            // The display name falls back to "Local" for clocks without an
+            // explicit `tz`, but the structured `tz` field always carries
+            // the actual resolved IANA id, matching the zone embedded in
+            // `instant` below.
+            let display_name = if clock.tz.is_some() {
+                tz.0.to_string()
+            }
+            else {
+                "Local".to_string()
+            };
+            let name = clock.name.clone().unwrap_or(display_name);
+
+            ClockRecord {
+                name,
+                tz: tz.0.to_string(),
+                local_time: local_time.format(format).to_string(),
+                offset: local_time.format("%:z").to_string(),
+                dst: local_time.format("%Z").to_string(),
+                instant: DateTimeTz { instant: time, tz },
+            }
+        })
+        .collect()
+}
+
+fn print_clocks(clocks: &[Clock], time: DateTime<Utc>, default_format: &str, output: OutputFormat) {
+    let records = clock_records(clocks, time, default_format);
+
+    match output {
+        OutputFormat::Table => {
+            let mut table = Table::new();
+            table.set_format(*FORMAT_CLEAN);
+
+            for record in &records {
+                table.add_row(Row::new(vec![
+                    Cell::new(&record.name).with_style(Attr::Bold),
+                    Cell::new(&record.local_time),
+                    Cell::new(&record.offset),
+                    Cell::new(&record.dst),
+                ]));
+            }
+
+            table.printstd();
+        }
+        OutputFormat::Json => {
+            match serde_json::to_string_pretty(&records) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to serialize clocks as JSON: {:#}", e),
+            }
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for record in &records {
+                if let Err(e) = writer.serialize(record) {
+                    eprintln!("Failed to serialize clock as CSV: {:#}", e);
+                    return;
+                }
+            }
+            let _ = writer.flush();
+        }
+    }
 }
 
-fn print_clocks(clocks: &[Clock], time: DateTime<Utc>) {
+/// Prints a meeting-planner grid: one row per clock, one column per hour of
+/// `day`. Columns are computed by stepping hour by hour in UTC from `day`'s
+/// midnight in the local timezone, then converting into each clock's zone,
+/// so the grid lines up the same instants across all of them.
+fn print_plan(clocks: &[Clock], day: NaiveDate) -> Result<(), Error> {
+    let midnight = day
+        .and_hms_opt(0, 0, 0)
+        .ok_or_else(|| eyre!("{} is not a valid day", day))?;
+    let midnight_utc = resolve_local(&Local, midnight)?;
+
     let mut table = Table::new();
     table.set_format(*FORMAT_CLEAN);
 
-    for clock in clocks {
-        let local_time;
-        let tz_name;
+    let mut header = Row::new(vec![Cell::new("")]);
+    for hour in 0..24 {
+        let instant = midnight_utc + Duration::hours(hour);
+        let label = instant.with_timezone(&Local).format("%H").to_string();
+        header.add_cell(Cell::new(&label).with_style(Attr::Bold));
+    }
+    table.add_row(header);
 
-        if let Some(tz) = &clock.tz {
-            local_time = time.with_timezone(&tz.0).naive_local();
-            tz_name = tz.0.to_string();
+    for clock in clocks {
+        let tz = clock.tz.clone().unwrap_or_else(local_tz);
+        let tz_name = if clock.tz.is_some() {
+            tz.0.to_string()
         }
         else {
-            local_time = time.with_timezone(&Local).naive_local();
-            tz_name = "Local".to_string();
-        }
+            "Local".to_string()
+        };
+        let name = clock.name.clone().unwrap_or(tz_name);
+
+        let mut row = Row::new(vec![Cell::new(&name).with_style(Attr::Bold)]);
+        for hour in 0..24 {
+            let local_time = (midnight_utc + Duration::hours(hour)).with_timezone(&tz.0);
+            let mut cell = Cell::new(&local_time.format("%H:%M").to_string());
 
-        let name = clock.name.as_ref().unwrap_or(&tz_name);
+            if let Some((start, end)) = clock.work_hours
+                && (start..end).contains(&local_time.hour())
+            {
+                cell = cell.with_style(Attr::ForegroundColor(color::GREEN));
+            }
 
-        table.add_row(Row::new(vec![
-            Cell::new(&name).with_style(Attr::Bold),
-            Cell::new(&local_time.format("%H:%M:%S").to_string()),
-        ]));
+            row.add_cell(cell);
+        }
+        table.add_row(row);
     }
 
     table.printstd();
+
+    Ok(())
+}
+
+/// Parses a bare (zone-less) date-time or time given on the command line.
+///
+/// A time without a date is combined with today's date *in `zone`*, since
+/// "today" depends on the zone the time will be interpreted in (e.g. a bare
+/// time given in a zone far ahead of UTC can still be "tomorrow" in UTC).
+fn parse_naive<Tz2>(s: &str, zone: &Tz2) -> Result<NaiveDateTime, Error>
+where
+    Tz2: TimeZone,
+{
+    if let Ok(naive) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return Ok(naive);
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M") {
+        return Ok(naive);
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(s, "%H:%M:%S") {
+        return Ok(Utc::now().with_timezone(zone).date_naive().and_time(time));
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(s, "%H:%M") {
+        return Ok(Utc::now().with_timezone(zone).date_naive().and_time(time));
+    }
+    Err(eyre!("Could not parse `{}` as a date and/or time", s))
+}
+
+/// Resolves a naive date-time in the given timezone, turning the
+/// [`LocalResult`] returned by [`TimeZone::from_local_datetime`] into a
+/// proper error instead of silently picking a result.
+///
+/// A [`LocalResult::None`] means the time falls in a DST gap (it never
+/// happened in this zone), and a [`LocalResult::Ambiguous`] means it falls
+/// in a DST overlap (it happened twice); both candidates are reported so
+/// the user can pick the one they meant, e.g. by specifying an explicit
+/// offset instead.
+fn resolve_local<Tz2>(tz: &Tz2, naive: NaiveDateTime) -> Result<DateTime<Utc>, Error>
+where
+    Tz2: TimeZone,
+{
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(dt) => Ok(dt.with_timezone(&Utc)),
+        LocalResult::None => {
+            Err(eyre!(
+                "{} does not exist in this timezone (it falls in a gap created by a \
+                 clock change, e.g. when clocks spring forward)",
+                naive
+            ))
+        }
+        LocalResult::Ambiguous(earlier, later) => {
+            Err(eyre!(
+                "{} is ambiguous in this timezone (it falls in an overlap created by a \
+                 clock change, e.g. when clocks fall back): could be {} or {}; specify an \
+                 explicit UTC offset to disambiguate",
+                naive,
+                earlier.to_rfc3339(),
+                later.to_rfc3339()
+            ))
+        }
+    }
+}
+
+/// Determines the [`DateTime<Utc>`] to display clocks for, based on the
+/// `--time`, `--in` and `--utc` arguments.
+fn parse_time(args: &Args) -> Result<DateTime<Utc>, Error> {
+    let Some(time) = &args.time
+    else {
+        if args.utc {
+            bail!("--utc can only be used together with --time.");
+        }
+        if args.in_tz.is_some() {
+            bail!("--in can only be used together with --time.");
+        }
+        return Ok(Utc::now());
+    };
+
+    if args.utc && args.in_tz.is_some() {
+        bail!("--utc can't be combined with --in.");
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(time) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    if args.utc {
+        resolve_local(&Utc, parse_naive(time, &Utc)?)
+    }
+    else if let Some(tz) = &args.in_tz {
+        resolve_local(&tz.0, parse_naive(time, &tz.0)?)
+    }
+    else {
+        resolve_local(&Local, parse_naive(time, &Local)?)
+    }
 }
 
 fn main() -> Result<(), Error> {
     let args = Args::from_args();
 
-    let config_path = if let Some(config_path) = args.config {
-        config_path
+    if args.watch && args.time.is_some() {
+        bail!("--watch can't be combined with --time.");
+    }
+
+    if args.plan && args.output != OutputFormat::Table {
+        bail!("--plan only supports --output table for now.");
+    }
+
+    let config_path = if let Some(config_path) = &args.config {
+        config_path.clone()
     }
     else {
         dirs::config_dir()
@@ -128,20 +479,34 @@ fn main() -> Result<(), Error> {
         config.clocks.push(Clock::default());
     }
 
-    // TODO: Parse the --time option properly from the command line
-    /*let time = match (args.time, args.utc) {
-        (Some(time), false) => Utc
-            .from_local_datetime(&time)
-            .single()
-            .ok_or_else(|| anyhow!("Conversion from local time failed. This can happen during time transition."))?,
-        (Some(time), true) => Utc.from_utc_datetime(&time),
-        (None, false) => Utc::now(),
-        (None, true) => bail!("--utc can only be used with --time."),
-    };*/
+    let default_format = config.format.as_deref().unwrap_or(DEFAULT_FORMAT).to_string();
 
-    let time = Utc::now();
+    let render = |time: DateTime<Utc>| -> Result<(), Error> {
+        if args.plan {
+            let day = args.day.unwrap_or_else(|| Local::now().date_naive());
+            print_plan(&config.clocks, day)
+        }
+        else {
+            print_clocks(&config.clocks, time, &default_format, args.output);
+            Ok(())
+        }
+    };
 
-    print_clocks(&config.clocks, time);
+    if args.watch {
+        loop {
+            // Clear the screen and move the cursor back to the top-left
+            // corner before redrawing.
+            print!("\x1B[2J\x1B[1;1H");
+            std::io::stdout().flush().ok();
+
+            render(Utc::now())?;
+
+            std::thread::sleep(std::time::Duration::from_secs(args.interval.max(1)));
+        }
+    }
+    else {
+        render(parse_time(&args)?)?;
+    }
 
     Ok(())
 }
@@ -167,3 +532,13 @@ impl<'de> Deserialize<'de> for Tz {
         s.parse().map(Self).map_err(serde::de::Error::custom)
     }
 }
+
+impl std::str::FromStr for Tz {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<chrono_tz::Tz>()
+            .map(Self)
+            .map_err(|e| e.to_string())
+    }
+}